@@ -1,60 +1,127 @@
 #![feature(proc_macro_diagnostic)]
 
-use anyhow::{bail, Result};
 use proc_macro::{Diagnostic, Level, Span, TokenStream};
 use quote::quote;
+use syn::spanned::Spanned;
 use syn::{parse_macro_input, Abi, FnArg, ItemFn};
 
-fn failure(callback: proc_macro2::TokenStream, error_message: &str) -> TokenStream {
-    Diagnostic::spanned(Span::call_site(), Level::Error, error_message).emit();
+fn failure(callback: proc_macro2::TokenStream, diagnostic: Diagnostic) -> TokenStream {
+    diagnostic.emit();
     callback.into()
 }
 
-fn determine_calling_convention(input: &ItemFn, unmangled_name: &str) -> Result<Abi> {
+fn determine_calling_convention(input: &ItemFn, unmangled_name: &str) -> Result<Abi, Diagnostic> {
     if let Some(abi) = &input.sig.abi {
-        let abi = quote! { #abi };
-        bail!("Detour functions cannot specify an ABI. The ABI is automatically specified by rivets. You specifed: {abi}");
-    } else {
-        let abi = rivets_shared::get_calling_convention(unmangled_name);
-        if let Some(abi) = abi {
-            Ok(abi)
-        } else {
-            bail!("Failed to determine calling convention for {unmangled_name}. Please report this issue to the rivets developers.");
+        let abi_tokens = quote! { #abi };
+        return Err(Diagnostic::spanned(
+            abi.span().unwrap(),
+            Level::Error,
+            "Detour functions cannot specify an ABI.",
+        )
+        .help(format!(
+            "the ABI is automatically determined from the symbol's calling convention; remove `{abi_tokens}`"
+        )));
+    }
+
+    rivets_shared::get_calling_convention(unmangled_name).ok_or_else(|| {
+        Diagnostic::spanned(
+            Span::call_site(),
+            Level::Error,
+            format!(
+                "Failed to determine calling convention for {unmangled_name}. Please report this issue to the rivets developers."
+            ),
+        )
+    })
+}
+
+/// The three argument-shaped token streams the generated code needs: plain types (for the
+/// `cpp_function_header` function-pointer type), `name: type` pairs (for `call_original`'s own
+/// signature), and bare names (to forward into `Detour.call(...)`).
+struct DetourArguments {
+    types: proc_macro2::TokenStream,
+    typed: proc_macro2::TokenStream,
+    names: proc_macro2::TokenStream,
+}
+
+fn collect_arguments(sig: &syn::Signature) -> Result<DetourArguments, Diagnostic> {
+    let mut types = Vec::new();
+    let mut typed = Vec::new();
+    let mut names = Vec::new();
+
+    for arg in &sig.inputs {
+        match arg {
+            FnArg::Receiver(receiver) => {
+                return Err(Diagnostic::spanned(
+                    receiver.span().unwrap(),
+                    Level::Error,
+                    "Detour functions cannot use the self parameter.",
+                )
+                .help(
+                    "write a plain function instead; rivets passes the C++ `this` pointer as an ordinary argument",
+                ));
+            }
+            FnArg::Typed(pat) => {
+                let attrs = &pat.attrs;
+                let ty = &pat.ty;
+                let arg_pat = &pat.pat;
+                types.push(quote! { #(#attrs)* #ty });
+                typed.push(quote! { #(#attrs)* #arg_pat: #ty });
+                names.push(quote! { #arg_pat });
+            }
         }
     }
+
+    Ok(DetourArguments {
+        types: quote! { #( #types ),* },
+        typed: quote! { #( #typed ),* },
+        names: quote! { #( #names ),* },
+    })
 }
 
 #[proc_macro_attribute]
 pub fn detour(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mangled_name = attr.to_string();
-    let unmangled_name =
-        rivets_shared::demangle(&mangled_name).unwrap_or_else(|| mangled_name.clone());
+    let attr_span = attr
+        .into_iter()
+        .next()
+        .map_or_else(Span::call_site, |token| token.span());
+
+    let unmangled_name = match rivets_shared::demangle(&mangled_name) {
+        Some(name) => name,
+        None => {
+            Diagnostic::spanned(
+                attr_span,
+                Level::Warning,
+                format!("Failed to demangle `{mangled_name}` as an MSVC or Itanium symbol; falling back to using it as-is."),
+            )
+            .help(format!("the raw mangled symbol is `{mangled_name}`"))
+            .span_help(
+                attr_span,
+                "double check this is the fully mangled name rivets reported for this symbol, not a demangled or partially-mangled one",
+            )
+            .emit();
+            mangled_name.clone()
+        }
+    };
 
     let input = parse_macro_input!(item as ItemFn);
     let callback = quote! { #input };
     let name = &input.sig.ident;
     let return_type = &input.sig.output;
 
-    let arguments: Vec<proc_macro2::TokenStream> = input
-        .sig
-        .inputs
-        .iter()
-        .map(|arg| match arg {
-            FnArg::Receiver(_) => {
-                quote! {compile_error!("Detour functions cannot use the self parameter.")}
-            }
-            FnArg::Typed(pat) => {
-                let attrs = &pat.attrs;
-                let ty = &pat.ty;
-                quote! { #(#attrs)* #ty }
-            }
-        })
-        .collect();
-    let arguments = quote! { #( #arguments ),* };
+    let arguments = match collect_arguments(&input.sig) {
+        Ok(arguments) => arguments,
+        Err(diagnostic) => return failure(callback, diagnostic),
+    };
+    let DetourArguments {
+        types: arguments,
+        typed: typed_arguments,
+        names: argument_names,
+    } = arguments;
 
     let calling_convention = match determine_calling_convention(&input, &unmangled_name) {
         Ok(abi) => abi,
-        Err(e) => return failure(callback, &e.to_string()),
+        Err(diagnostic) => return failure(callback, diagnostic),
     };
 
     let cpp_function_header = quote! {
@@ -65,11 +132,25 @@ pub fn detour(attr: TokenStream, item: TokenStream) -> TokenStream {
         #[doc = #unmangled_name]
         #callback
 
-        unsafe fn hook(address: u64) -> anyhow::Result<()> {
-            retour::static_detour! {
-                static Detour: #cpp_function_header;
-            }
+        retour::static_detour! {
+            static Detour: #cpp_function_header;
+        }
 
+        /// Calls the original, un-hooked implementation of this function through the
+        /// detour's trampoline.
+        ///
+        /// # Safety
+        /// Must only be called after `hook` below has initialized and enabled `Detour`, i.e.
+        /// from within the body of this detour. Always go through this trampoline rather than
+        /// calling the raw hooked address directly: the detour has overwritten the start of the
+        /// original function with a jump, and only the trampoline knows how to execute the
+        /// overwritten bytes before continuing into the rest of the original function.
+        #[allow(dead_code)]
+        unsafe fn call_original(#typed_arguments) #return_type {
+            Detour.call(#argument_names)
+        }
+
+        unsafe fn hook(address: u64) -> anyhow::Result<()> {
             let compiled_function: #cpp_function_header = std::mem::transmute(address);
             Detour.initialize(compiled_function, #name)?.enable()?;
             Ok(())
@@ -78,7 +159,7 @@ pub fn detour(attr: TokenStream, item: TokenStream) -> TokenStream {
         #[ctor::ctor]
         fn ctor() {
             rivets::start_stream();
-            if let Err(e) = rivets::inject(#mangled_name, hook) {
+            if let Err(e) = rivets::register(#mangled_name, hook) {
                 tracing::error!("{e}");
             }
         }
@@ -1,59 +1,117 @@
 use anyhow::{bail, Result};
 use cpp_demangle::Symbol;
 use dirs::home_dir;
-use pdb::{FallibleIterator, PDB};
+use once_cell::sync::OnceCell;
+use pdb::{FallibleIterator, PDBInformation, PDB};
+use serde::{Deserialize, Serialize};
 use std::ffi::CString;
+use std::fs::File;
 use std::net::TcpStream;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use std::{collections::HashMap, fs::File};
-use std::{ffi::CStr, path::PathBuf};
-use tracing::info;
+use std::time::Duration;
+use std::{collections::HashMap, ffi::CStr};
+use tracing::{debug, info, warn};
 use undname::Flags;
 use windows::core::PCSTR;
 use windows::Win32::System::LibraryLoader::GetModuleHandleA;
 
+/// The `hook` function generated by `#[detour]`: given the resolved address of the original
+/// function, sets up and enables the `retour` detour.
+type Hook = unsafe fn(u64) -> Result<()>;
+
+/// On-disk representation of a built symbol map, keyed by the PDB's GUID/age so a cache built
+/// against a different `factorio.pdb` is never mistaken for a match.
+#[derive(Serialize, Deserialize)]
+struct CachedSymbolMap {
+    guid: [u8; 16],
+    age: u32,
+    symbol_addresses: HashMap<String, u32>,
+}
+
+/// A process-wide cache of `factorio.pdb`'s public function symbols. Building this requires a
+/// full walk of the symbol table, so it is built at most once per process via [`PDB_CACHE`]
+/// and, where possible, loaded straight from an on-disk cache instead.
 struct PDBCache {
-    pdb: PDB<'static, File>,
     symbol_addresses: HashMap<String, u32>,
     base_address: u64,
 }
 
 impl PDBCache {
     fn new(pdb_path: &Path, module_name: &str) -> Result<Self> {
-        let file = File::open(pdb_path)?;
-        let pdb = PDB::open(file)?;
         let base_address = unsafe { get_dll_base_address(module_name)? };
+        let symbol_addresses = Self::load_or_build_symbol_map(pdb_path)?;
 
-        let mut cache = Self {
-            pdb,
-            symbol_addresses: HashMap::new(),
+        Ok(Self {
+            symbol_addresses,
             base_address,
+        })
+    }
+
+    /// Loads the symbol map from the on-disk cache next to `pdb_path` if one exists and its
+    /// GUID/age matches, otherwise walks the PDB's symbol table and writes a fresh cache.
+    fn load_or_build_symbol_map(pdb_path: &Path) -> Result<HashMap<String, u32>> {
+        let file = File::open(pdb_path)?;
+        let mut pdb = PDB::open(file)?;
+        let info = pdb.pdb_information()?;
+        let cache_path = pdb_path.with_extension("rivets-cache");
+
+        if let Some(symbol_addresses) = Self::read_cache(&cache_path, &info) {
+            debug!(
+                "Loaded {} cached symbol addresses from {}",
+                symbol_addresses.len(),
+                cache_path.display()
+            );
+            return Ok(symbol_addresses);
+        }
+
+        let symbol_addresses = Self::build_symbol_map(&mut pdb)?;
+        Self::write_cache(&cache_path, &info, &symbol_addresses);
+        Ok(symbol_addresses)
+    }
+
+    fn read_cache(cache_path: &Path, info: &PDBInformation) -> Option<HashMap<String, u32>> {
+        let bytes = std::fs::read(cache_path).ok()?;
+        let cached: CachedSymbolMap = bincode::deserialize(&bytes).ok()?;
+
+        (cached.guid == *info.guid.as_bytes() && cached.age == info.age)
+            .then_some(cached.symbol_addresses)
+    }
+
+    fn write_cache(cache_path: &Path, info: &PDBInformation, symbol_addresses: &HashMap<String, u32>) {
+        let cached = CachedSymbolMap {
+            guid: *info.guid.as_bytes(),
+            age: info.age,
+            symbol_addresses: symbol_addresses.clone(),
         };
 
-        cache.build_symbol_map()?;
+        let result = bincode::serialize(&cached)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| std::fs::write(cache_path, bytes).map_err(anyhow::Error::from));
 
-        Ok(cache)
+        if let Err(e) = result {
+            warn!("Failed to persist symbol cache to {}: {e}", cache_path.display());
+        }
     }
 
-    fn build_symbol_map(&mut self) -> Result<()> {
-        let symbol_table = self.pdb.global_symbols()?;
-        let address_map = self.pdb.address_map()?;
+    fn build_symbol_map(pdb: &mut PDB<'_, File>) -> Result<HashMap<String, u32>> {
+        let symbol_table = pdb.global_symbols()?;
+        let address_map = pdb.address_map()?;
+        let mut symbol_addresses = HashMap::new();
 
         symbol_table
             .iter()
             .for_each(|symbol| match symbol.parse() {
                 Ok(pdb::SymbolData::Public(data)) if data.function => {
                     let rva = data.offset.to_rva(&address_map).unwrap_or_default();
-                    self.symbol_addresses
-                        .insert(data.name.to_string().into(), rva.0);
+                    symbol_addresses.insert(data.name.to_string().into(), rva.0);
                     Ok(())
                 }
                 Err(e) => Err(e),
                 _ => Ok(()),
             })?;
 
-        Ok(())
+        Ok(symbol_addresses)
     }
 
     fn get_function_address(&self, function_name: &str) -> Option<u64> {
@@ -72,26 +130,95 @@ unsafe fn get_dll_base_address(module_name: &str) -> Result<u64> {
     }
 }
 
-pub fn inject(function_name: &str, hook: unsafe fn(u64) -> Result<()>) -> Result<()> {
+/// The process-wide `PDBCache`, built at most once no matter how many detours register.
+static PDB_CACHE: OnceCell<Mutex<PDBCache>> = OnceCell::new();
+
+/// Detours that have registered but not yet been resolved against [`PDB_CACHE`].
+static PENDING_HOOKS: Mutex<Vec<(String, Hook)>> = Mutex::new(Vec::new());
+
+/// Queues a detour for resolution, then immediately resolves everything queued so far. Each
+/// `#[detour]`'s generated `ctor` calls this instead of resolving its own address. Ctors run
+/// one at a time, so in practice this resolves one hook per call -- the actual batching win is
+/// that the expensive full walk of the `factorio.pdb` symbol table only ever happens once,
+/// since the first call builds (or loads from disk) the shared `PDBCache` and every later call
+/// reuses it via [`PDB_CACHE`]'s [`OnceCell`].
+pub fn register(function_name: &str, hook: Hook) -> Result<()> {
+    PENDING_HOOKS
+        .lock()
+        .unwrap()
+        .push((function_name.to_string(), hook));
+
+    resolve_pending()
+}
+
+fn resolve_pending() -> Result<()> {
     let pdb_path = factorio_path("factorio.pdb")?;
-    let pdb_cache = PDBCache::new(&pdb_path, "factorio.exe")?;
+    let cache =
+        PDB_CACHE.get_or_try_init(|| PDBCache::new(&pdb_path, "factorio.exe").map(Mutex::new))?;
+    let cache = cache.lock().unwrap();
 
-    let Some(address) = pdb_cache.get_function_address(function_name) else {
-        bail!("Failed to find {function_name} address");
-    };
-    info!("{} address: {:#x}", function_name, address);
+    for (function_name, hook) in std::mem::take(&mut *PENDING_HOOKS.lock().unwrap()) {
+        let Some(address) = cache.get_function_address(&function_name) else {
+            tracing::error!("Failed to find {function_name} address");
+            continue;
+        };
+        info!("{function_name} address: {address:#x}");
+        if let Err(e) = unsafe { hook(address) } {
+            tracing::error!("Failed to install hook for {function_name}: {e}");
+            continue;
+        }
+    }
+
+    Ok(())
+}
+
+/// Connects to the injector's log listener, retrying with exponential backoff instead of
+/// panicking if the port isn't accepting connections yet (e.g. the injector hasn't started
+/// listening when the DLL is injected).
+fn connect_with_backoff(ip: &str) -> TcpStream {
+    let mut backoff = Duration::from_millis(100);
 
-    unsafe { hook(address) }
+    loop {
+        match TcpStream::connect(ip) {
+            Ok(stream) => return stream,
+            Err(e) => {
+                // No `tracing` subscriber is installed yet at this point, so this has to go to
+                // stderr directly.
+                eprintln!("Could not connect to the rivets log listener at {ip} ({e}), retrying in {backoff:?}");
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(Duration::from_secs(5));
+            }
+        }
+    }
 }
 
+/// The port the DLL connects to for log streaming, and the injector listens on, when neither
+/// side has been told otherwise. The injector passes its actual bound port to the game process
+/// as `RIVETS_LOG_PORT`, so this default only matters if the DLL is injected by some other means.
+/// Shared with `InjectorConfig::resolve` so the two sides can never drift apart again.
+pub const DEFAULT_LOG_PORT: u16 = 40267;
+
+/// Starts streaming this process's `tracing` events to the injector as line-delimited JSON, so
+/// the injector can deserialize and re-render each event (with its own level/target filtering)
+/// rather than receiving pre-formatted text. The port defaults to [`DEFAULT_LOG_PORT`] but is
+/// overridden by `RIVETS_LOG_PORT`, which the injector sets on the Factorio process it launches.
+/// Every `#[detour]`'s generated `ctor` calls this; only the first call connects and installs
+/// the subscriber, later calls do nothing.
 pub fn start_stream() {
-    let ip = "127.0.0.1:40267";
-    let stream = TcpStream::connect(ip).unwrap_or_else(|_| {
-        panic!("Could not establish stdout connection to rivets. Port {ip} is busy.")
+    static STARTED: std::sync::Once = std::sync::Once::new();
+
+    STARTED.call_once(|| {
+        let port = std::env::var("RIVETS_LOG_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(DEFAULT_LOG_PORT);
+        let ip = format!("127.0.0.1:{port}");
+        let stream = connect_with_backoff(&ip);
+        tracing_subscriber::fmt::fmt()
+            .json()
+            .with_writer(Mutex::new(stream))
+            .init();
     });
-    tracing_subscriber::fmt::fmt()
-        .with_writer(Mutex::new(stream))
-        .init();
 }
 
 pub trait AsPcstr {
@@ -104,16 +231,58 @@ impl AsPcstr for CStr {
     }
 }
 
-pub fn factorio_path(filename: &str) -> Result<PathBuf> {
-    let factorio_path = home_dir();
-
-    if let Some(mut path) = factorio_path {
-        path.push(r"Documents\factorio\bin\x64\");
-        path.push(filename);
-        Ok(path)
-    } else {
-        bail!("Failed to find the user's home directory.")
+/// The layouts rivets knows how to find a Factorio install under, relative to a home directory
+/// or drive root: Steam libraries, a standalone (non-Steam) install, and the portable
+/// `Documents` location rivets has always defaulted to.
+fn candidate_factorio_dirs() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    if let Some(home) = home_dir() {
+        candidates.push(home.join(r"AppData\Local\Programs\Steam\steamapps\common\Factorio\bin\x64"));
+        candidates.push(home.join(r"Documents\factorio\bin\x64"));
     }
+
+    for drive in ["C:", "D:", "E:"] {
+        candidates.push(PathBuf::from(format!(
+            r"{drive}\Program Files (x86)\Steam\steamapps\common\Factorio\bin\x64"
+        )));
+        candidates.push(PathBuf::from(format!(
+            r"{drive}\SteamLibrary\steamapps\common\Factorio\bin\x64"
+        )));
+        candidates.push(PathBuf::from(format!(r"{drive}\Factorio\bin\x64")));
+    }
+
+    candidates
+}
+
+/// Searches the common Factorio install layouts for a directory containing both
+/// `factorio.exe` and `factorio.pdb`, failing with every location searched if none match.
+pub fn discover_factorio_install() -> Result<PathBuf> {
+    candidate_factorio_dirs()
+        .into_iter()
+        .find(|dir| dir.join("factorio.exe").is_file() && dir.join("factorio.pdb").is_file())
+        .ok_or_else(|| {
+            let searched = candidate_factorio_dirs()
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n  ");
+            anyhow::anyhow!(
+                "Could not find a Factorio install with both factorio.exe and factorio.pdb. Searched:\n  {searched}"
+            )
+        })
+}
+
+/// Resolves `filename` inside the Factorio install: `RIVETS_FACTORIO_DIR` (set by the injector
+/// once it has resolved and verified an install) takes priority, falling back to
+/// auto-discovery when the DLL was injected by some other means.
+pub fn factorio_path(filename: &str) -> Result<PathBuf> {
+    let dir = match std::env::var_os("RIVETS_FACTORIO_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => discover_factorio_install()?,
+    };
+
+    Ok(dir.join(filename))
 }
 
 /// Attempts to demangle a mangled MSVC C++ symbol name. First tries MSVC demangling, then falls back to Itanium.
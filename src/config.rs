@@ -0,0 +1,179 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_MOD_DLL: &str = r"target\debug\examplemod.dll";
+
+/// On-disk `rivets.toml` configuration. Every field is optional: anything left unset falls
+/// through to the matching CLI flag's default, which is itself either a hardcoded default or
+/// the result of auto-discovery.
+#[derive(Debug, Default, Deserialize)]
+struct RivetsToml {
+    factorio_dir: Option<PathBuf>,
+    mod_dll: Option<PathBuf>,
+    log_port: Option<u16>,
+}
+
+/// The injector's fully-resolved configuration, after layering CLI flags over `rivets.toml`
+/// over auto-discovery.
+#[derive(Debug)]
+pub struct InjectorConfig {
+    pub factorio_dir: PathBuf,
+    pub factorio_exe: PathBuf,
+    pub mod_dll: PathBuf,
+    pub log_port: u16,
+}
+
+impl InjectorConfig {
+    /// Resolves the configuration from `args` (CLI flags: `--config`, `--factorio-dir`,
+    /// `--mod-dll`, `--log-port`) and a `rivets.toml` in the current working directory (or the
+    /// file given by `--config`), auto-discovering
+    /// the Factorio install if neither specifies one. Verifies the resolved
+    /// `factorio.exe`/`factorio.pdb` pair looks like a matched set before returning.
+    pub fn resolve(args: &[String]) -> Result<Self> {
+        let mut config_path = PathBuf::from("rivets.toml");
+        let mut factorio_dir = None;
+        let mut mod_dll = None;
+        let mut log_port = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => config_path = args.next().map_or(config_path, PathBuf::from),
+                "--factorio-dir" => factorio_dir = args.next().map(PathBuf::from),
+                "--mod-dll" => mod_dll = args.next().map(PathBuf::from),
+                "--log-port" => log_port = args.next().and_then(|port| port.parse().ok()),
+                _ => {}
+            }
+        }
+
+        let toml_config = std::fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|contents| toml::from_str::<RivetsToml>(&contents).ok())
+            .unwrap_or_default();
+
+        let factorio_dir = match factorio_dir.or(toml_config.factorio_dir) {
+            Some(dir) => dir,
+            None => rivets_shared::discover_factorio_install()?,
+        };
+        let factorio_exe = factorio_dir.join("factorio.exe");
+        let factorio_pdb = factorio_dir.join("factorio.pdb");
+        verify_matching_versions(&factorio_exe, &factorio_pdb)?;
+
+        Ok(Self {
+            factorio_dir,
+            factorio_exe,
+            mod_dll: mod_dll
+                .or(toml_config.mod_dll)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_MOD_DLL)),
+            log_port: log_port
+                .or(toml_config.log_port)
+                .unwrap_or(rivets_shared::DEFAULT_LOG_PORT),
+        })
+    }
+}
+
+/// Checks that `factorio.exe` and `factorio.pdb` belong to the same build by comparing the
+/// CodeView GUID/age the linker stamped into the PE's debug directory against the GUID/age in
+/// the PDB header itself -- the same identifier `rivets-shared`'s `PDBCache` already keys its
+/// on-disk symbol cache by. Unlike a modification-time check, this can't be fooled by a
+/// freshly-extracted mismatched pair, and can't be spuriously tripped by Steam touching one file
+/// during a verify/repair.
+fn verify_matching_versions(exe: &Path, pdb: &Path) -> Result<()> {
+    let (exe_guid, exe_age) = read_pe_codeview_id(exe)
+        .with_context(|| format!("Failed to read the debug directory of {}", exe.display()))?;
+    let (pdb_guid, pdb_age) = read_pdb_id(pdb)
+        .with_context(|| format!("Failed to read the PDB header of {}", pdb.display()))?;
+
+    if exe_guid != pdb_guid || exe_age != pdb_age {
+        bail!(
+            "{} (GUID {exe_guid:?}, age {exe_age}) and {} (GUID {pdb_guid:?}, age {pdb_age}) are mismatched versions; reinstall Factorio or regenerate the PDB",
+            exe.display(),
+            pdb.display(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Reads the GUID/age the PDB backend stamped into its own header, the same value
+/// `PDBInformation::guid`/`age` expose to `rivets-shared`'s `PDBCache`.
+fn read_pdb_id(pdb_path: &Path) -> Result<([u8; 16], u32)> {
+    let file = File::open(pdb_path)?;
+    let mut pdb = pdb::PDB::open(file)?;
+    let info = pdb.pdb_information()?;
+
+    Ok((*info.guid.as_bytes(), info.age))
+}
+
+/// Reads the GUID/age out of a PE's CodeView (RSDS) debug directory entry -- the same
+/// identifier the linker stamped into the matching PDB's header, making this the field to
+/// compare to detect a mismatched exe/pdb pair. Only 64-bit (PE32+) images are supported, which
+/// is the only format modern Factorio ships.
+fn read_pe_codeview_id(exe_path: &Path) -> Result<([u8; 16], u32)> {
+    const IMAGE_DIRECTORY_ENTRY_DEBUG: usize = 6;
+    const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+    const PE32_PLUS_MAGIC: u16 = 0x20b;
+    const SECTION_HEADER_SIZE: usize = 40;
+
+    let bytes = std::fs::read(exe_path)?;
+    let u16_at = |offset: usize| -> Result<u16> {
+        Ok(u16::from_le_bytes(bytes.get(offset..offset + 2).context("truncated PE")?.try_into()?))
+    };
+    let u32_at = |offset: usize| -> Result<u32> {
+        Ok(u32::from_le_bytes(bytes.get(offset..offset + 4).context("truncated PE")?.try_into()?))
+    };
+
+    let nt_headers = u32_at(0x3c)? as usize;
+    anyhow::ensure!(bytes.get(nt_headers..nt_headers + 4) == Some(b"PE\0\0".as_slice()), "missing PE signature");
+
+    let coff_header = nt_headers + 4;
+    let number_of_sections = u16_at(coff_header + 2)? as usize;
+    let size_of_optional_header = u16_at(coff_header + 16)? as usize;
+
+    let optional_header = coff_header + 20;
+    anyhow::ensure!(u16_at(optional_header)? == PE32_PLUS_MAGIC, "only PE32+ (64-bit) images are supported");
+
+    let data_directory = optional_header + 112;
+    let debug_entry = data_directory + IMAGE_DIRECTORY_ENTRY_DEBUG * 8;
+    let debug_rva = u32_at(debug_entry)?;
+    let debug_size = u32_at(debug_entry + 4)? as usize;
+    anyhow::ensure!(debug_size >= 28, "debug directory entry too small");
+
+    // IMAGE_DEBUG_DIRECTORY lives in section data (typically .rdata/.buildid), not the headers,
+    // so its RVA has to be mapped through the section table to a real file offset before it can
+    // be indexed into `bytes` -- unlike `PointerToRawData` below, which is already a file offset.
+    let section_table = optional_header + size_of_optional_header;
+    let rva_to_file_offset = |rva: u32| -> Result<usize> {
+        for i in 0..number_of_sections {
+            let section = section_table + i * SECTION_HEADER_SIZE;
+            let virtual_size = u32_at(section + 8)?;
+            let virtual_address = u32_at(section + 12)?;
+            let pointer_to_raw_data = u32_at(section + 20)?;
+
+            if rva >= virtual_address && rva < virtual_address + virtual_size {
+                return Ok((pointer_to_raw_data + (rva - virtual_address)) as usize);
+            }
+        }
+
+        bail!("RVA {rva:#x} is not contained in any section")
+    };
+
+    let debug_offset = rva_to_file_offset(debug_rva)?;
+    let debug_type = u32_at(debug_offset + 12)?;
+    anyhow::ensure!(debug_type == IMAGE_DEBUG_TYPE_CODEVIEW, "no CodeView debug directory entry");
+    let codeview_offset = u32_at(debug_offset + 24)? as usize;
+
+    anyhow::ensure!(
+        bytes.get(codeview_offset..codeview_offset + 4) == Some(b"RSDS".as_slice()),
+        "debug directory entry is not an RSDS CodeView record"
+    );
+    let guid = bytes
+        .get(codeview_offset + 4..codeview_offset + 20)
+        .context("truncated CodeView record")?
+        .try_into()?;
+    let age = u32_at(codeview_offset + 20)?;
+
+    Ok((guid, age))
+}
@@ -1,5 +1,9 @@
+mod config;
+
+use config::InjectorConfig;
 use dll_syringe::{process::OwnedProcess, Syringe};
-use std::io;
+use serde::Deserialize;
+use std::io::{BufRead, BufReader};
 use std::net::TcpListener;
 use windows::core::{PCSTR, PSTR};
 use windows::Win32::Foundation::CloseHandle;
@@ -32,22 +36,51 @@ fn inject_dll(dll_name: &str) {
     }
 }
 
-fn start_factorio(factorio_path: &str) -> Result<PROCESS_INFORMATION, String> {
+/// Builds an ANSI `CreateProcessA` environment block: the current process's environment,
+/// followed by `extra_vars`, each `"KEY=value\0"`, double-null terminated. Current-environment
+/// entries whose key is also in `extra_vars` are skipped, so e.g. a stale `RIVETS_FACTORIO_DIR`
+/// the injector inherited from its own shell can never shadow the one we just resolved.
+fn build_environment_block(extra_vars: &[(&str, String)]) -> Vec<u8> {
+    let mut block = Vec::new();
+
+    for (key, value) in std::env::vars() {
+        if extra_vars.iter().any(|(extra_key, _)| *extra_key == key) {
+            continue;
+        }
+        block.extend_from_slice(format!("{key}={value}\0").as_bytes());
+    }
+    for (key, value) in extra_vars {
+        block.extend_from_slice(format!("{key}={value}\0").as_bytes());
+    }
+    block.push(0);
+
+    block
+}
+
+fn start_factorio(config: &InjectorConfig) -> Result<PROCESS_INFORMATION, String> {
     let mut startup_info: STARTUPINFOA = unsafe { std::mem::zeroed() };
     startup_info.cb = std::mem::size_of::<STARTUPINFOA>() as u32;
     let mut factorio_process_information: PROCESS_INFORMATION = unsafe { std::mem::zeroed() };
-    startup_info.cb = std::mem::size_of::<STARTUPINFOA>() as u32;
+
+    // Hand the resolved install directory and log port down to the DLL via the environment, so
+    // `rivets_shared::factorio_path`/`start_stream` use the exact same configuration instead of
+    // re-discovering (and potentially disagreeing with) it from inside the game process.
+    let environment = build_environment_block(&[
+        ("RIVETS_FACTORIO_DIR", config.factorio_dir.display().to_string()),
+        ("RIVETS_LOG_PORT", config.log_port.to_string()),
+    ]);
+    let factorio_exe = config.factorio_exe.to_string_lossy();
 
     println!("Creating factorio.exe process...");
     let process_result = unsafe {
         CreateProcessA(
-            factorio_path.as_pcstr(),
+            factorio_exe.as_ref().as_pcstr(),
             PSTR::null(),
             None,
             None,
             false,
             CREATE_SUSPENDED,
-            None,
+            Some(environment.as_ptr().cast()),
             PCSTR::null(),
             &mut startup_info,
             &mut factorio_process_information,
@@ -71,24 +104,149 @@ unsafe fn get_dll_base_address(module_name: &str) -> Result<u64, String> {
     }
 }
 
+/// A single `tracing_subscriber::fmt().json()` event, as received from the injected DLL.
+#[derive(Deserialize)]
+struct LogEvent {
+    timestamp: String,
+    level: String,
+    target: String,
+    #[serde(default)]
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Severities ordered from most to least severe, so a configured threshold shows that level
+/// and everything more severe than it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_uppercase().as_str() {
+            "ERROR" => Self::Error,
+            "WARN" => Self::Warn,
+            "INFO" => Self::Info,
+            "DEBUG" => Self::Debug,
+            "TRACE" => Self::Trace,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Which events to print, configured from CLI flags: `--level <LEVEL>` shows that level and
+/// anything more severe, `--target <TARGET>` shows only events whose `target` matches exactly.
+struct LogFilter {
+    max_level: LogLevel,
+    target: Option<String>,
+}
+
+impl LogFilter {
+    fn from_args(args: &[String]) -> Self {
+        let mut max_level = LogLevel::Trace;
+        let mut target = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--level" => {
+                    if let Some(level) = args.next() {
+                        max_level = level.parse().unwrap_or(LogLevel::Trace);
+                    }
+                }
+                "--target" => target = args.next().cloned(),
+                _ => {}
+            }
+        }
+
+        Self { max_level, target }
+    }
+
+    fn allows(&self, event: &LogEvent) -> bool {
+        let level_allowed = event
+            .level
+            .parse::<LogLevel>()
+            .is_ok_and(|level| level <= self.max_level);
+        let target_allowed = self
+            .target
+            .as_ref()
+            .is_none_or(|target| &event.target == target);
+
+        level_allowed && target_allowed
+    }
+}
+
+/// Reads newline-delimited JSON log events from the mod's connection and re-renders the ones
+/// that pass `filter` locally. Each event is its own line, so `BufRead::lines` already gives us
+/// the framing guarantee that a partial read never hands us half an event.
+fn stream_logs(listener: &TcpListener, filter: &LogFilter) {
+    let Ok((stream, _)) = listener.accept() else {
+        eprintln!("Failed to accept the mod's log connection.");
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            eprintln!("Lost the mod's log connection.");
+            break;
+        };
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<LogEvent>(&line) {
+            Ok(event) if filter.allows(&event) => print_event(&event),
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to parse a log event ({e}): {line}"),
+        }
+    }
+}
+
+fn print_event(event: &LogEvent) {
+    let message = event
+        .fields
+        .get("message")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default();
+
+    println!(
+        "{} {:>5} {}: {message}",
+        event.timestamp, event.level, event.target
+    );
+}
+
 fn main() {
-    let dll_path = r"target\debug\examplemod.dll";
+    let args: Vec<String> = std::env::args().collect();
+
+    let config = match InjectorConfig::resolve(&args[1..]) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
 
-    let listener = match TcpListener::bind("127.0.55.1:16337") {
+    let listener = match TcpListener::bind(("127.0.0.1", config.log_port)) {
         Ok(listener) => listener,
         Err(e) => {
             eprintln!(
-                "Failed to copy the Factorio output logs. Is rivets already running?\n{}",
-                e
+                "Failed to listen for the mod's log stream on port {}. Is rivets already running?\n{}",
+                config.log_port, e
             );
             return;
         }
     };
 
-    let factorio_path = r"C:\Users\zacha\Documents\factorio\bin\x64\factorio.exe";
     let factorio_process_information: PROCESS_INFORMATION;
 
-    match start_factorio(factorio_path) {
+    match start_factorio(&config) {
         Ok(pi) => factorio_process_information = pi,
         Err(e) => {
             eprintln!("{}", e);
@@ -97,7 +255,7 @@ fn main() {
     }
     let process_handle = factorio_process_information.hProcess;
 
-    inject_dll(&dll_path);
+    inject_dll(&config.mod_dll.to_string_lossy());
 
     let base_address = unsafe { get_dll_base_address("factorio.exe") }.unwrap();
     println!("Factorio base address: {:?}", base_address);
@@ -108,10 +266,7 @@ fn main() {
         CloseHandle(process_handle).ok();
     }
 
-    // Duplicate the factorio stdout stream onto our own stdout.
-    io::copy(
-        &mut listener.incoming().next().unwrap().unwrap(),
-        &mut io::stdout(),
-    )
-    .unwrap();
+    // Stream the mod's structured log events onto our own stdout.
+    let filter = LogFilter::from_args(&args[1..]);
+    stream_logs(&listener, &filter);
 }
@@ -4,6 +4,7 @@ use std::{
 };
 
 use lazy_regex::{regex, regex_is_match};
+use quote::{format_ident, quote};
 
 /// The full type name including namespaces and template types. ie `std::vector<int>`
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -280,3 +281,290 @@ impl Symbol {
         result
     }
 }
+
+/// MSVC calling convention keywords recognized inside a demangled function signature, in the
+/// form `rivets_shared::get_calling_convention` expects them.
+const CALLING_CONVENTIONS: [&str; 5] = [
+    "__cdecl",
+    "__stdcall",
+    "__fastcall",
+    "__thiscall",
+    "__vectorcall",
+];
+
+/// Rust primitive type names `cpp_type_to_rust_type` can produce, used to tell a pointer to a
+/// primitive apart from a pointer to an opaque class type when collecting struct stubs.
+const RUST_PRIMITIVES: [&str; 12] = [
+    "bool", "i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "f32", "f64", "()",
+];
+
+// This impl block contains the binding-generation functions, which turn a demangled MSVC
+// function signature into a Rust declaration a modder can write a `#[detour]` against.
+impl Symbol {
+    /// Finds the function's argument list parentheses at template-depth 0, so commas/parens
+    /// nested inside a template argument of the return type (e.g. `std::function<void(int)>`)
+    /// aren't mistaken for the argument list. Returns a pair of everything before the opening
+    /// paren and the contents between it and its matching closing paren.
+    fn split_args_paren(signature: &str) -> Option<(&str, &str)> {
+        let chars: Vec<(usize, char)> = signature.char_indices().collect();
+        let mut template_depth = 0i32;
+
+        for (i, &(open_offset, c)) in chars.iter().enumerate() {
+            match c {
+                '<' => template_depth += 1,
+                '>' => template_depth -= 1,
+                '(' if template_depth == 0 => {
+                    let mut paren_depth = 0i32;
+                    for &(offset, c) in &chars[i..] {
+                        match c {
+                            '(' => paren_depth += 1,
+                            ')' => {
+                                paren_depth -= 1;
+                                if paren_depth == 0 {
+                                    return Some((
+                                        &signature[..open_offset],
+                                        &signature[open_offset + 1..offset],
+                                    ));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    return None;
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Splits a parameter list by its top-level commas, the same way `templates_vec` splits
+    /// template arguments: a comma nested inside a template's `<...>` doesn't start a new
+    /// parameter.
+    fn split_params(params: &str) -> Vec<String> {
+        let params = params.trim();
+        if params.is_empty() || params == "void" {
+            return Vec::new();
+        }
+
+        let mut result = Vec::new();
+        let mut current = String::new();
+        let mut template_depth = 0i32;
+
+        for c in params.chars() {
+            match c {
+                '<' | '(' => {
+                    template_depth += 1;
+                    current.push(c);
+                }
+                '>' | ')' => {
+                    template_depth -= 1;
+                    current.push(c);
+                }
+                ',' if template_depth == 0 => {
+                    result.push(current.trim().to_string());
+                    current = String::new();
+                }
+                _ => current.push(c),
+            }
+        }
+
+        if !current.trim().is_empty() {
+            result.push(current.trim().to_string());
+        }
+
+        result
+    }
+
+    /// Maps a demangled C++ parameter or return type (e.g. `std::vector<int> const &`,
+    /// `MyClass *`) to the Rust type an FFI caller should use for it. Known primitives map
+    /// directly; anything else is treated as an opaque class type referenced by pointer, since
+    /// rivets never owns C++ objects by value.
+    fn cpp_type_to_rust_type(cpp_type: &str) -> syn::Type {
+        let cpp_type = cpp_type.trim();
+        let indirection = cpp_type
+            .chars()
+            .rev()
+            .take_while(|c| matches!(c, '*' | '&' | ' '))
+            .filter(|c| matches!(c, '*' | '&'))
+            .count();
+        let base = cpp_type.trim_end_matches(['*', '&', ' ']);
+        let base = base
+            .trim_start_matches("const ")
+            .trim_end_matches(" const")
+            .trim();
+
+        if let Some(ty) = Self::primitive_rust_type(base) {
+            let mut ty = ty;
+            for _ in 0..indirection {
+                ty = syn::parse_quote! { *mut #ty };
+            }
+            return ty;
+        }
+
+        // Opaque class types are always referenced by pointer, since rivets never owns C++
+        // objects by value, even if the C++ signature passes the class by value.
+        let opaque_ident = Self::opaque_struct_ident(base);
+        let mut ty: syn::Type = syn::parse_quote! { #opaque_ident };
+        for _ in 0..indirection.max(1) {
+            ty = syn::parse_quote! { *mut #ty };
+        }
+        ty
+    }
+
+    fn primitive_rust_type(cpp_type: &str) -> Option<syn::Type> {
+        Some(match cpp_type {
+            "bool" => syn::parse_quote! { bool },
+            "char" | "signed char" => syn::parse_quote! { i8 },
+            "unsigned char" => syn::parse_quote! { u8 },
+            "short" | "short int" => syn::parse_quote! { i16 },
+            "unsigned short" | "unsigned short int" => syn::parse_quote! { u16 },
+            "int" | "long" | "long int" => syn::parse_quote! { i32 },
+            "unsigned int" | "unsigned long" | "unsigned long int" => syn::parse_quote! { u32 },
+            "long long" | "long long int" | "__int64" => syn::parse_quote! { i64 },
+            "unsigned long long" | "unsigned long long int" | "unsigned __int64" => {
+                syn::parse_quote! { u64 }
+            }
+            "float" => syn::parse_quote! { f32 },
+            "double" => syn::parse_quote! { f64 },
+            "void" => syn::parse_quote! { () },
+            _ => return None,
+        })
+    }
+
+    /// Turns a fully-qualified C++ class name into a Rust identifier for the opaque
+    /// `#[repr(C)]` struct standing in for it, e.g. `my_namespace::MyClass` -> `my_namespace_MyClass`.
+    fn opaque_struct_ident(fully_qualified: &str) -> syn::Ident {
+        let joined = Self::new(fully_qualified.to_string())
+            .namespace_vec()
+            .join("_");
+
+        syn::parse_str(&joined).unwrap_or_else(|_| format_ident!("UnknownType"))
+    }
+
+    /// Parses a full demangled MSVC function signature -- e.g.
+    /// `virtual void __thiscall my_namespace::MyClass::DoThing(int, float const &)` --
+    /// into the matching Rust declaration: the return type, argument types and calling
+    /// convention `#[detour]` already knows how to generate, reconstructed here instead of
+    /// hand-written by the modder. Rust has no built-in notion of the MSVC `this` pointer, so
+    /// for a non-static member function -- identified by MSVC's `__thiscall`, the only calling
+    /// convention it uses for one -- it is added as an explicit leading argument, the same way
+    /// rivets passes it as an ordinary argument to `#[detour]` hooks.
+    pub fn to_rust_fn_signature(demangled: &str) -> Option<syn::Signature> {
+        let demangled = demangled.trim().trim_start_matches("virtual ").trim();
+        let (head, params) = Self::split_args_paren(demangled)?;
+
+        let (cc_offset, cc) = CALLING_CONVENTIONS
+            .iter()
+            .find_map(|cc| head.find(cc).map(|offset| (offset, *cc)))?;
+        let return_type = head[..cc_offset].trim();
+        let fully_qualified_name = head[cc_offset + cc.len()..].trim();
+        let abi = rivets_shared::get_calling_convention(cc)?;
+
+        let mut namespace_vec = Self::new(fully_qualified_name.to_string()).namespace_vec();
+        let function_name = namespace_vec.pop()?;
+        let ident = syn::parse_str::<syn::Ident>(&function_name).ok()?;
+
+        let output = if return_type == "void" {
+            syn::ReturnType::Default
+        } else {
+            let ty = Self::cpp_type_to_rust_type(return_type);
+            syn::parse_quote! { -> #ty }
+        };
+
+        let this_arg: Option<syn::FnArg> = (cc == "__thiscall" && !namespace_vec.is_empty())
+            .then(|| {
+                let class_ident = Self::opaque_struct_ident(&namespace_vec.join("::"));
+                syn::parse_quote! { this: *mut #class_ident }
+            });
+
+        let inputs: Vec<syn::FnArg> = this_arg
+            .into_iter()
+            .chain(Self::split_params(params).iter().enumerate().map(|(i, param)| {
+                let ty = Self::cpp_type_to_rust_type(param);
+                let arg_name = format_ident!("arg{i}");
+                syn::parse_quote! { #arg_name: #ty }
+            }))
+            .collect();
+
+        Some(syn::parse_quote! {
+            #abi fn #ident(#(#inputs),*) #output
+        })
+    }
+}
+
+/// Walks a generated signature's argument and return types, collecting the identifier of every
+/// opaque class type referenced by pointer so `generate_bindings` can emit a struct stub for it.
+fn collect_opaque_idents(signature: &syn::Signature, out: &mut Vec<syn::Ident>) {
+    fn visit(ty: &syn::Type, out: &mut Vec<syn::Ident>) {
+        let syn::Type::Ptr(ptr) = ty else { return };
+        let syn::Type::Path(path) = ptr.elem.as_ref() else {
+            return visit(&ptr.elem, out);
+        };
+        let Some(ident) = path.path.get_ident() else {
+            return;
+        };
+
+        if !RUST_PRIMITIVES.contains(&ident.to_string().as_str()) && !out.contains(ident) {
+            out.push(ident.clone());
+        }
+    }
+
+    for arg in &signature.inputs {
+        if let syn::FnArg::Typed(pat) = arg {
+            visit(&pat.ty, out);
+        }
+    }
+    if let syn::ReturnType::Type(_, ty) = &signature.output {
+        visit(ty, out);
+    }
+}
+
+/// Batches a list of mangled PDB symbol names into a single generated Rust bindings file: one
+/// `extern "<abi>" { ... }` declaration per resolvable function signature, plus an opaque
+/// `#[repr(C)]` struct stub for every class type referenced by pointer, so a modder can
+/// `#[detour]` a C++ member function without hand-writing its argument types.
+#[must_use]
+pub fn generate_bindings(mangled_names: &[String]) -> String {
+    let mut opaque_idents: Vec<syn::Ident> = Vec::new();
+    let mut declarations = proc_macro2::TokenStream::new();
+
+    for mangled in mangled_names {
+        let Some(demangled) = rivets_shared::demangle(mangled) else {
+            continue;
+        };
+        let Some(mut signature) = Symbol::to_rust_fn_signature(&demangled) else {
+            continue;
+        };
+
+        collect_opaque_idents(&signature, &mut opaque_idents);
+
+        // The ABI goes on the enclosing `extern` block; items inside it cannot repeat it.
+        let abi = signature
+            .abi
+            .take()
+            .expect("to_rust_fn_signature always sets an abi");
+        declarations.extend(quote! {
+            #abi {
+                #[doc = #demangled]
+                #signature;
+            }
+        });
+    }
+
+    let opaque_structs = opaque_idents.iter().map(|ident| {
+        quote! {
+            #[repr(C)]
+            pub struct #ident {
+                _private: [u8; 0],
+            }
+        }
+    });
+
+    quote! {
+        #(#opaque_structs)*
+        #declarations
+    }
+    .to_string()
+}